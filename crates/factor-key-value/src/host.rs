@@ -7,6 +7,8 @@ use std::{collections::HashSet, sync::Arc};
 use tracing::{instrument, Level};
 
 const DEFAULT_STORE_TABLE_CAPACITY: u32 = 256;
+const DEFAULT_CURSOR_TABLE_CAPACITY: u32 = 256;
+const DEFAULT_LIST_KEYS_PAGE_SIZE: u64 = 1000;
 
 pub use key_value::Error;
 
@@ -31,12 +33,215 @@ pub trait Store: Sync + Send {
     async fn delete(&self, key: &str) -> Result<(), Error>;
     async fn exists(&self, key: &str) -> Result<bool, Error>;
     async fn get_keys(&self) -> Result<Vec<String>, Error>;
+
+    /// Get the value of each of `keys`, preserving input order.
+    ///
+    /// Keys with no value are represented as `None` rather than being omitted, so callers can
+    /// line up results with the keys they asked for.
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<Option<(String, Vec<u8>)>>, Error> {
+        let mut results = Vec::with_capacity(keys.len());
+        for key in keys {
+            let value = self.get(&key).await?;
+            results.push(value.map(|value| (key, value)));
+        }
+        Ok(results)
+    }
+
+    /// Set multiple key/value pairs in one call.
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        for (key, value) in key_values {
+            self.set(&key, &value).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete multiple keys in one call.
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        for key in keys {
+            self.delete(&key).await?;
+        }
+        Ok(())
+    }
+
+    /// Atomically add `delta` to the integer stored at `key` (treating a missing key as zero),
+    /// returning the new value.
+    ///
+    /// The default implementation is a plain `get` followed by a `set` with no lock between them,
+    /// so it is not atomic: concurrent increments of the same key can lose updates.
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        let current = parse_current(self.get(&key).await?)?;
+        let new_value = current + delta;
+        self.set(&key, new_value.to_string().as_bytes()).await?;
+        Ok(new_value)
+    }
+
+    /// Begin a compare-and-swap operation on `key`, returning a handle for reading the current
+    /// value/version and then attempting a single conditional write.
+    async fn new_compare_and_swap(&self, key: &str) -> Result<Arc<dyn Cas>, Error>;
+
+    /// List a page of keys, starting at `cursor` (inclusive) and containing at most `limit` keys,
+    /// along with an opaque cursor for the next page, or `None` once there are no more keys.
+    ///
+    /// `cursor` is the continuation cursor returned by a previous call, or `None` to start from
+    /// the beginning. The cursor is the first key that has not yet been returned, not the last
+    /// key that was. (An inclusive "last key returned" cursor would
+    /// land back on that same key via `partition_point` below, so the same key would repeat on
+    /// every subsequent page forever.) The default implementation sorts and pages through the
+    /// full result of [`Store::get_keys`]; backends with a native paginated listing API (e.g. a
+    /// range query with a server-side page limit) should override this rather than loading every
+    /// key up front, but must keep the same cursor convention.
+    async fn list_keys(
+        &self,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> Result<(Vec<String>, Option<String>), Error> {
+        let mut keys = self.get_keys().await?;
+        keys.sort();
+
+        let start = match &cursor {
+            Some(cursor) => keys.partition_point(|key| key.as_str() < cursor.as_str()),
+            None => 0,
+        };
+        let limit = usize::try_from(limit.unwrap_or(u64::MAX)).unwrap_or(usize::MAX).max(1);
+        let end = keys.len().min(start.saturating_add(limit));
+
+        let page = keys[start..end].to_vec();
+        let next_cursor = (end < keys.len()).then(|| keys[end].clone());
+
+        Ok((page, next_cursor))
+    }
+
+    /// Begin a transaction that accumulates `set`/`delete` operations to later [`Store::commit`]
+    /// atomically.
+    fn begin(&self) -> Transaction {
+        Transaction::default()
+    }
+
+    /// Apply `transaction`'s operations all-or-nothing: if one op fails partway through, every op
+    /// already applied is rolled back to the value it held before `commit` was called.
+    ///
+    /// The default implementation has no backend-native transaction to apply the ops under, so it
+    /// applies them one at a time via `set`/`delete`, remembering each key's prior value as it
+    /// goes; on failure it restores (or re-deletes) every key it already touched, in reverse
+    /// order. This is a best-effort substitute for a real transaction, not an airtight one: a
+    /// concurrent reader can still observe a partially-applied commit while it's in flight (there
+    /// is no backend-wide lock), and if a rollback write itself fails, the store is left
+    /// partially applied -- that secondary failure is logged rather than returned, since the
+    /// original error is what the caller needs to see. A backend with native transactions should
+    /// override this with a real staged/batched apply instead. `options.durable` echoes fxfs's
+    /// transaction options: when true, the commit should not return until the write(s) are
+    /// confirmed durable; the default implementation is unaffected either way since it has no
+    /// queued writes to wait on.
+    async fn commit(&self, transaction: Transaction, options: Options) -> Result<(), Error> {
+        let _ = options;
+
+        let mut applied: Vec<(String, Option<Vec<u8>>)> = Vec::with_capacity(transaction.ops.len());
+
+        for op in transaction.ops {
+            let key = match &op {
+                TransactionOp::Set(key, _) | TransactionOp::Delete(key) => key.clone(),
+            };
+            let result = match self.get(&key).await {
+                Ok(previous) => match &op {
+                    TransactionOp::Set(_, value) => self.set(&key, value).await,
+                    TransactionOp::Delete(_) => self.delete(&key).await,
+                }
+                .map(|()| previous),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(previous) => applied.push((key, previous)),
+                Err(e) => {
+                    for (key, previous) in applied.into_iter().rev() {
+                        let rollback = match previous {
+                            Some(value) => self.set(&key, &value).await,
+                            None => self.delete(&key).await,
+                        };
+                        if let Err(rollback_err) = rollback {
+                            tracing::warn!(
+                                "failed to roll back key {key:?} after a failed transaction: {rollback_err:?}"
+                            );
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A set of staged `set`/`delete` operations to be applied atomically by [`Store::commit`].
+#[derive(Default)]
+pub struct Transaction {
+    pub(crate) ops: Vec<TransactionOp>,
+}
+
+pub enum TransactionOp {
+    Set(String, Vec<u8>),
+    Delete(String),
+}
+
+impl Transaction {
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<Vec<u8>>) -> &mut Self {
+        self.ops.push(TransactionOp::Set(key.into(), value.into()));
+        self
+    }
+
+    pub fn delete(&mut self, key: impl Into<String>) -> &mut Self {
+        self.ops.push(TransactionOp::Delete(key.into()));
+        self
+    }
+}
+
+/// Options controlling how a [`Transaction`] is committed, echoing fxfs's transaction options.
+#[derive(Clone, Copy, Default)]
+pub struct Options {
+    /// Whether [`Store::commit`] should wait for the write(s) to reach durable storage before
+    /// returning. `false` keeps today's fire-and-forget (non-durable) behavior; `true` gives the
+    /// guest real feedback on persistence, at the cost of waiting for it.
+    pub durable: bool,
+}
+
+/// A handle for performing a single optimistic-concurrency read-modify-write against the value
+/// of the key it was created for.
+///
+/// The version token returned by [`Cas::current`] is opaque to callers: it must be passed back
+/// to [`Cas::swap`] unmodified, and a backend is free to encode it however it likes (e.g. a
+/// monotonically increasing per-key version number, or a hash of the stored bytes for backends
+/// with no native versioning). `swap` fails safely -- by returning `Ok(false)` rather than an
+/// error -- when the token no longer matches the stored version, signalling the caller to re-read
+/// via `current` and retry rather than risk clobbering a concurrent write.
+#[async_trait]
+pub trait Cas: Sync + Send {
+    /// Returns the current value, if any, along with an opaque token describing its version.
+    async fn current(&self) -> Result<(Option<Vec<u8>>, String), Error>;
+
+    /// Writes `value` if the version is still `token`, returning `false` if it is not.
+    async fn swap(&self, value: Vec<u8>, token: String) -> Result<bool, Error>;
+}
+
+/// Parse a stored value as the decimal string representation of an `i64`, treating an absent
+/// value as zero.
+pub(crate) fn parse_current(value: Option<Vec<u8>>) -> Result<i64, Error> {
+    match value {
+        Some(bytes) => std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or_else(|| Error::Other("existing value is not an integer".to_string())),
+        None => Ok(0),
+    }
 }
 
 pub struct KeyValueDispatch {
     allowed_stores: HashSet<String>,
     manager: Arc<dyn StoreManager>,
     stores: Table<Arc<dyn Store>>,
+    // Maps the opaque `u64` cursor handed to wasi `list-keys` callers to the `Store`-level string
+    // cursor it stands for, since the wasi wit interface's cursor is a handle, not the key itself.
+    cursors: Table<String>,
 }
 
 impl KeyValueDispatch {
@@ -53,6 +258,7 @@ impl KeyValueDispatch {
             allowed_stores,
             manager,
             stores: Table::new(capacity),
+            cursors: Table::new(DEFAULT_CURSOR_TABLE_CAPACITY),
         }
     }
 
@@ -72,6 +278,27 @@ impl KeyValueDispatch {
             .get(store.rep())
             .ok_or(wasi_keyvalue::store::Error::NoSuchStore)
     }
+
+    /// Commit a batch of `set`/`delete` operations against `store` atomically, optionally
+    /// waiting for the write to become durable before returning.
+    ///
+    /// This is the host-side building block for the `write-durable` support the module docs
+    /// anticipate, but it is not yet reachable by anything: it's exposed as a plain method on
+    /// `KeyValueDispatch` rather than a wit world trait impl, and neither `key_value::HostStore`
+    /// nor `wasi_keyvalue::store::HostBucket` call it, so no guest can invoke it and no host
+    /// embedder calls it either today. Binding it to a guest needs a `transaction` resource added
+    /// to key-value.wit, which this change does not attempt -- that's a wit schema change with
+    /// its own review, out of scope here. Treat this method as internal scaffolding until that
+    /// lands.
+    pub async fn commit_transaction<T: 'static>(
+        &mut self,
+        store: Resource<T>,
+        ops: Vec<TransactionOp>,
+        durable: bool,
+    ) -> anyhow::Result<Result<(), Error>> {
+        let store = self.get_store(store)?;
+        Ok(store.commit(Transaction { ops }, Options { durable }).await)
+    }
 }
 
 #[async_trait]
@@ -230,15 +457,32 @@ impl wasi_keyvalue::store::HostBucket for KeyValueDispatch {
         self_: Resource<Bucket>,
         cursor: Option<u64>,
     ) -> Result<wasi_keyvalue::store::KeyResponse, wasi_keyvalue::store::Error> {
-        if cursor.unwrap_or_default() != 0 {
-            return Err(wasi_keyvalue::store::Error::Other(
-                "list_keys: cursor not supported".to_owned(),
-            ));
-        }
+        // The wasi cursor is a handle into `self.cursors`, not the `Store`-level cursor itself,
+        // since the wit interface's cursor is a plain `u64` rather than an opaque blob.
+        let cursor = cursor
+            .map(|handle| {
+                self.cursors.get(handle).cloned().ok_or_else(|| {
+                    wasi_keyvalue::store::Error::Other("list_keys: invalid cursor".to_owned())
+                })
+            })
+            .transpose()?;
 
         let store = self.get_store_wasi(self_)?;
-        let keys = store.get_keys().await.map_err(to_wasi_err)?;
-        Ok(wasi_keyvalue::store::KeyResponse { keys, cursor: None })
+        let (keys, next_cursor) = store
+            .list_keys(cursor, Some(DEFAULT_LIST_KEYS_PAGE_SIZE))
+            .await
+            .map_err(to_wasi_err)?;
+
+        let cursor = next_cursor
+            .map(|next_cursor| {
+                self.cursors.push(next_cursor).map_err(|()| {
+                    wasi_keyvalue::store::Error::Other("list_keys: cursor table full".to_owned())
+                })
+            })
+            .transpose()?
+            .map(u64::from);
+
+        Ok(wasi_keyvalue::store::KeyResponse { keys, cursor })
     }
 
     async fn drop(&mut self, rep: Resource<Bucket>) -> anyhow::Result<()> {
@@ -312,3 +556,129 @@ impl spin_world::v1::key_value::Host for KeyValueDispatch {
         <Self as key_value::HostStore>::drop(self, this).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A [`Store`] with an in-memory key set, just enough to exercise the default
+    /// [`Store::list_keys`] implementation.
+    struct MockStore {
+        keys: Vec<&'static str>,
+    }
+
+    #[async_trait]
+    impl Store for MockStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.keys.contains(&key).then(Vec::new))
+        }
+
+        async fn set(&self, _key: &str, _value: &[u8]) -> Result<(), Error> {
+            unimplemented!()
+        }
+
+        async fn delete(&self, _key: &str) -> Result<(), Error> {
+            unimplemented!()
+        }
+
+        async fn exists(&self, key: &str) -> Result<bool, Error> {
+            Ok(self.keys.contains(&key))
+        }
+
+        async fn get_keys(&self) -> Result<Vec<String>, Error> {
+            Ok(self.keys.iter().map(|key| key.to_string()).collect())
+        }
+
+        async fn new_compare_and_swap(&self, _key: &str) -> Result<Arc<dyn Cas>, Error> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn list_keys_advances_past_the_page_boundary_without_repeating_it() {
+        let store = MockStore {
+            keys: vec!["a", "b", "c", "d"],
+        };
+
+        let (first_page, cursor) = store.list_keys(None, Some(2)).await.unwrap();
+        assert_eq!(first_page, vec!["a".to_string(), "b".to_string()]);
+        let cursor = cursor.expect("more keys remain");
+
+        let (second_page, cursor) = store.list_keys(Some(cursor), Some(2)).await.unwrap();
+        assert_eq!(second_page, vec!["c".to_string(), "d".to_string()]);
+        assert!(cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn list_keys_with_a_limit_of_one_advances_every_call() {
+        let store = MockStore { keys: vec!["a", "b"] };
+
+        let (page, cursor) = store.list_keys(None, Some(1)).await.unwrap();
+        assert_eq!(page, vec!["a".to_string()]);
+        let cursor = cursor.expect("more keys remain");
+
+        let (page, cursor) = store.list_keys(Some(cursor), Some(1)).await.unwrap();
+        assert_eq!(page, vec!["b".to_string()]);
+        assert!(cursor.is_none());
+    }
+
+    /// A [`Store`] backed by a plain map whose `set` fails for one designated key, just enough to
+    /// exercise the default [`Store::commit`]'s rollback-on-failure behavior.
+    struct FailingStore {
+        data: std::sync::Mutex<std::collections::HashMap<String, Option<Vec<u8>>>>,
+        fail_on: &'static str,
+    }
+
+    #[async_trait]
+    impl Store for FailingStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.data.lock().unwrap().get(key).cloned().flatten())
+        }
+
+        async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+            if key == self.fail_on {
+                return Err(Error::Other("simulated failure".to_string()));
+            }
+            self.data.lock().unwrap().insert(key.to_string(), Some(value.to_vec()));
+            Ok(())
+        }
+
+        async fn delete(&self, key: &str) -> Result<(), Error> {
+            self.data.lock().unwrap().insert(key.to_string(), None);
+            Ok(())
+        }
+
+        async fn exists(&self, key: &str) -> Result<bool, Error> {
+            Ok(self.get(key).await?.is_some())
+        }
+
+        async fn get_keys(&self) -> Result<Vec<String>, Error> {
+            unimplemented!()
+        }
+
+        async fn new_compare_and_swap(&self, _key: &str) -> Result<Arc<dyn Cas>, Error> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn commit_rolls_back_already_applied_ops_when_a_later_one_fails() {
+        let store = FailingStore {
+            data: std::sync::Mutex::new(std::collections::HashMap::from([(
+                "a".to_string(),
+                Some(b"orig".to_vec()),
+            )])),
+            fail_on: "b",
+        };
+
+        let mut transaction = store.begin();
+        transaction.set("a", b"new".to_vec());
+        transaction.set("b", b"new".to_vec());
+
+        store.commit(transaction, Options::default()).await.unwrap_err();
+
+        // "a" was already applied by the time "b" failed; it should have been rolled back to its
+        // pre-commit value rather than left at "new".
+        assert_eq!(store.get("a").await.unwrap(), Some(b"orig".to_vec()));
+    }
+}
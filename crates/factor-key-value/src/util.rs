@@ -1,14 +1,14 @@
-use crate::{Cas, Error, Store, StoreManager};
-use lru::LruCache;
+use crate::{parse_current, Cas, Error, Options, Store, StoreManager, Transaction, TransactionOp};
+use moka::future::Cache;
 use spin_core::async_trait;
 use std::{
     collections::{HashMap, HashSet},
     future::Future,
-    num::NonZeroUsize,
     sync::Arc,
+    time::Duration,
 };
 use tokio::{
-    sync::Mutex as AsyncMutex,
+    sync::{mpsc, Mutex as AsyncMutex},
     task::{self, JoinHandle},
 };
 use tracing::Instrument;
@@ -66,36 +66,65 @@ impl StoreManager for DelegatingStoreManager {
 /// the backing store once added to a cache since this implementation is intended for use only by short-lived guest
 /// instances.
 ///
-/// Note that, because writes are asynchronous and return immediately, durability is _not_ guaranteed.  I/O errors
-/// may occur asynchronously after the write operation has returned control to the guest, which may result in the
-/// write being lost without the guest knowing.  In the future, a separate `write-durable` function could be added
-/// to key-value.wit to provide either synchronous or asynchronous feedback on durability for guests which need it.
+/// Note that, because writes are asynchronous and return immediately, durability is _not_ guaranteed by default.  I/O
+/// errors may occur asynchronously after the write operation has returned control to the guest, which may result in
+/// the write being lost without the guest knowing.  A guest that needs synchronous feedback on persistence can opt in
+/// via [`Store::commit`] with [`Options::durable`] set, which awaits the write-behind task chain before returning.
+///
+/// The read cache itself is backed by `moka`, which supports concurrent, largely lock-free
+/// access and bounds itself by total cached bytes (via a weigher) rather than entry count, since
+/// key-value blobs vary wildly in size.  Durability still flows through an ordered write-behind
+/// task queue, independent of the read cache, so a `flush()` before a read or `get_keys` continues
+/// to guarantee read-your-writes even though moka may evict or expire entries concurrently.
 pub struct CachingStoreManager<T> {
-    capacity: NonZeroUsize,
+    max_capacity_bytes: u64,
+    time_to_live: Option<Duration>,
+    time_to_idle: Option<Duration>,
     inner: T,
 }
 
-const DEFAULT_CACHE_SIZE: usize = 256;
+const DEFAULT_MAX_CAPACITY_BYTES: u64 = 64 * 1024 * 1024;
 
 impl<T> CachingStoreManager<T> {
     pub fn new(inner: T) -> Self {
-        Self::new_with_capacity(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap(), inner)
+        Self::new_with_capacity(DEFAULT_MAX_CAPACITY_BYTES, None, None, inner)
     }
 
-    pub fn new_with_capacity(capacity: NonZeroUsize, inner: T) -> Self {
-        Self { capacity, inner }
+    pub fn new_with_capacity(
+        max_capacity_bytes: u64,
+        time_to_live: Option<Duration>,
+        time_to_idle: Option<Duration>,
+        inner: T,
+    ) -> Self {
+        Self {
+            max_capacity_bytes,
+            time_to_live,
+            time_to_idle,
+            inner,
+        }
     }
 }
 
 #[async_trait]
 impl<T: StoreManager> StoreManager for CachingStoreManager<T> {
     async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let mut builder = Cache::builder()
+            .max_capacity(self.max_capacity_bytes)
+            .weigher(|key: &String, value: &Option<Vec<u8>>| -> u32 {
+                let bytes = key.len() + value.as_ref().map(Vec::len).unwrap_or(0);
+                bytes.try_into().unwrap_or(u32::MAX)
+            });
+        if let Some(ttl) = self.time_to_live {
+            builder = builder.time_to_live(ttl);
+        }
+        if let Some(tti) = self.time_to_idle {
+            builder = builder.time_to_idle(tti);
+        }
+
         Ok(Arc::new(CachingStore {
             inner: self.inner.get(name).await?,
-            state: AsyncMutex::new(CachingStoreState {
-                cache: LruCache::new(self.capacity),
-                previous_task: None,
-            }),
+            cache: builder.build(),
+            queue: Arc::new(AsyncMutex::new(WriteBehindQueue { previous_task: None })),
         }))
     }
 
@@ -108,12 +137,15 @@ impl<T: StoreManager> StoreManager for CachingStoreManager<T> {
     }
 }
 
-struct CachingStoreState {
-    cache: LruCache<String, Option<Vec<u8>>>,
+/// The ordered queue of pending backing-store writes for a [`CachingStore`].
+///
+/// This is kept independent of the (moka-backed) read cache so that write order and durability
+/// guarantees don't depend on whatever the cache decides to evict or expire.
+struct WriteBehindQueue {
     previous_task: Option<JoinHandle<Result<(), Error>>>,
 }
 
-impl CachingStoreState {
+impl WriteBehindQueue {
     /// Wrap the specified task in an outer task which waits for `self.previous_task` before proceeding, and spawn
     /// the result.  This ensures that write order is preserved.
     fn spawn(&mut self, task: impl Future<Output = Result<(), Error>> + Send + 'static) {
@@ -143,7 +175,8 @@ impl CachingStoreState {
 
 struct CachingStore {
     inner: Arc<dyn Store>,
-    state: AsyncMutex<CachingStoreState>,
+    cache: Cache<String, Option<Vec<u8>>>,
+    queue: Arc<AsyncMutex<WriteBehindQueue>>,
 }
 
 #[async_trait]
@@ -151,20 +184,18 @@ impl Store for CachingStore {
     async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
         // Retrieve the specified value from the cache, lazily populating the cache as necessary.
 
-        let mut state = self.state.lock().await;
-
-        if let Some(value) = state.cache.get(key).cloned() {
+        if let Some(value) = self.cache.get(key).await {
             return Ok(value);
         }
 
         // Flush any outstanding writes prior to reading from store.  This is necessary because we need to
-        // guarantee the guest will read its own writes even if entries have been popped off the end of the LRU
-        // cache prior to their corresponding writes reaching the backing store.
-        state.flush().await?;
+        // guarantee the guest will read its own writes even if entries have been evicted from the cache prior
+        // to their corresponding writes reaching the backing store.
+        self.queue.lock().await.flush().await?;
 
         let value = self.inner.get(key).await?;
 
-        state.cache.put(key.to_owned(), value.clone());
+        self.cache.insert(key.to_owned(), value.clone()).await;
 
         Ok(value)
     }
@@ -172,14 +203,15 @@ impl Store for CachingStore {
     async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
         // Update the cache and spawn a task to update the backing store asynchronously.
 
-        let mut state = self.state.lock().await;
-
-        state.cache.put(key.to_owned(), Some(value.to_owned()));
+        self.cache.insert(key.to_owned(), Some(value.to_owned())).await;
 
         let inner = self.inner.clone();
         let key = key.to_owned();
         let value = value.to_owned();
-        state.spawn(async move { inner.set(&key, &value).await });
+        self.queue
+            .lock()
+            .await
+            .spawn(async move { inner.set(&key, &value).await });
 
         Ok(())
     }
@@ -187,13 +219,14 @@ impl Store for CachingStore {
     async fn delete(&self, key: &str) -> Result<(), Error> {
         // Update the cache and spawn a task to update the backing store asynchronously.
 
-        let mut state = self.state.lock().await;
-
-        state.cache.put(key.to_owned(), None);
+        self.cache.insert(key.to_owned(), None).await;
 
         let inner = self.inner.clone();
         let key = key.to_owned();
-        state.spawn(async move { inner.delete(&key).await });
+        self.queue
+            .lock()
+            .await
+            .spawn(async move { inner.delete(&key).await });
 
         Ok(())
     }
@@ -209,52 +242,1211 @@ impl Store for CachingStore {
         // Note that we don't bother caching the result, since we expect this function won't be called more than
         // once for a given store in normal usage, and maintaining consistency would be complicated.
 
-        let mut state = self.state.lock().await;
+        // Flush any outstanding writes first in case entries have been evicted from the cache prior to their
+        // corresponding writes reaching the backing store.
+        self.queue.lock().await.flush().await?;
 
-        // Flush any outstanding writes first in case entries have been popped off the end of the LRU cache prior
-        // to their corresponding writes reaching the backing store.
-        state.flush().await?;
+        // Snapshot the cache once rather than looking up each backing-store key individually; `iter` doesn't
+        // refresh time-to-idle the way `get` would.
+        let overrides = self
+            .cache
+            .iter()
+            .map(|(k, v)| ((*k).clone(), v))
+            .collect::<HashMap<_, _>>();
 
         Ok(self
             .inner
             .get_keys()
             .await?
             .into_iter()
-            .filter(|k| {
-                state
-                    .cache
-                    .peek(k)
-                    .map(|v| v.as_ref().is_some())
-                    .unwrap_or(true)
-            })
+            .filter(|k| overrides.get(k).map(|v| v.is_some()).unwrap_or(true))
             .chain(
-                state
-                    .cache
-                    .iter()
-                    .filter_map(|(k, v)| v.as_ref().map(|_| k.to_owned())),
+                overrides
+                    .into_iter()
+                    .filter_map(|(k, v)| v.is_some().then_some(k)),
             )
             .collect::<HashSet<_>>()
             .into_iter()
             .collect())
     }
 
-    async fn get_many(&self, keys: Vec<String>) -> anyhow::Result<Vec<Option<(String, Vec<u8>)>>, Error> {
-        todo!()
+    async fn list_keys(
+        &self,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> Result<(Vec<String>, Option<String>), Error> {
+        // Flush first so the page we hand back reflects the backing store, then drop any keys
+        // that the cache knows have since been deleted.
+        self.queue.lock().await.flush().await?;
+
+        let (page, next_cursor) = self.inner.list_keys(cursor, limit).await?;
+
+        let overrides = self
+            .cache
+            .iter()
+            .map(|(k, v)| ((*k).clone(), v))
+            .collect::<HashMap<_, _>>();
+
+        let page = page
+            .into_iter()
+            .filter(|k| overrides.get(k).map(|v| v.is_some()).unwrap_or(true))
+            .collect();
+
+        Ok((page, next_cursor))
+    }
+
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<Option<(String, Vec<u8>)>>, Error> {
+        // Serve whatever we can from the cache, then flush and fall back to a single batched
+        // fetch of the backing store for the rest, so wrapping/backing stores that implement
+        // `get_many` natively (e.g. a backend's multi-key fetch) still get to batch the miss.
+
+        let mut results = vec![None; keys.len()];
+        let mut miss_indices = Vec::new();
+        let mut miss_keys = Vec::new();
+
+        for (index, key) in keys.iter().enumerate() {
+            if let Some(value) = self.cache.get(key).await {
+                results[index] = value.map(|value| (key.clone(), value));
+            } else {
+                miss_indices.push(index);
+                miss_keys.push(key.clone());
+            }
+        }
+
+        if !miss_keys.is_empty() {
+            self.queue.lock().await.flush().await?;
+
+            let fetched = self.inner.get_many(miss_keys.clone()).await?;
+            for ((index, key), entry) in miss_indices.into_iter().zip(miss_keys).zip(fetched) {
+                // Cache the miss either way, including `None`, so a repeat `get`/`get_many` for a
+                // key that doesn't exist doesn't re-flush and re-fetch every time.
+                let value = entry.as_ref().map(|(_, value)| value.clone());
+                self.cache.insert(key, value).await;
+                results[index] = entry;
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        // Update the cache for every key and spawn a single task to update the backing store
+        // asynchronously, chained after any previous write so order is preserved.
+
+        for (key, value) in &key_values {
+            self.cache.insert(key.clone(), Some(value.clone())).await;
+        }
+
+        let inner = self.inner.clone();
+        self.queue
+            .lock()
+            .await
+            .spawn(async move { inner.set_many(key_values).await });
+
+        Ok(())
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        // Update the cache for every key and spawn a single task to update the backing store
+        // asynchronously, chained after any previous write so order is preserved.
+
+        for key in &keys {
+            self.cache.insert(key.clone(), None).await;
+        }
+
+        let inner = self.inner.clone();
+        self.queue
+            .lock()
+            .await
+            .spawn(async move { inner.delete_many(keys).await });
+
+        Ok(())
     }
 
-    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> anyhow::Result<(), Error> {
-        todo!()
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        // Flush outstanding writes and read straight from the backing store so the increment is
+        // computed against the value it actually has, then update the cache and enqueue the
+        // write. The whole read-modify-write span is done under `queue`'s lock -- not just the
+        // flush and the spawn -- since `cache` itself is lock-free and two concurrent increments
+        // that both read before either writes would otherwise compute (and write) the same new
+        // value, silently dropping one of the increments.
+        let mut queue = self.queue.lock().await;
+        queue.flush().await?;
+
+        let current = self.inner.get(&key).await?;
+        let new_value = parse_current(current)? + delta;
+        let new_bytes = new_value.to_string().into_bytes();
+
+        self.cache.insert(key.clone(), Some(new_bytes.clone())).await;
+
+        let inner = self.inner.clone();
+        queue.spawn(async move { inner.set(&key, &new_bytes).await });
+
+        Ok(new_value)
     }
 
-    async fn delete_many(&self, keys: Vec<String>) -> anyhow::Result<(), Error> {
-        todo!()
+    async fn new_compare_and_swap(&self, key: &str) -> Result<Arc<dyn Cas>, Error> {
+        // Flush pending writes first so the CAS we hand back reads and writes against the
+        // backing store rather than a value the cache may no longer agree with.
+        self.queue.lock().await.flush().await?;
+
+        Ok(Arc::new(CachingCas {
+            inner: self.inner.new_compare_and_swap(key).await?,
+            cache: self.cache.clone(),
+            key: key.to_owned(),
+        }))
     }
 
-    async fn increment(&self, key: String, delta: i64) -> anyhow::Result<i64, Error> {
-        todo!()
+    async fn commit(&self, transaction: Transaction, options: Options) -> Result<(), Error> {
+        // Update the cache for every op, then enqueue a single write-behind task -- run under the
+        // queue lock, so it's ordered after (and durability-waits correctly wait for) any prior
+        // write -- that applies them to the backing store one at a time, rolling back whatever
+        // it already applied if one of them fails (see `Store::commit`'s doc for why this is
+        // best-effort, not airtight).
+        //
+        // This does *not* make the cache updates below atomic: `cache` is a lock-free `moka`
+        // cache independent of `queue` (see the module docs), so a concurrent `get`/`get_many`
+        // can observe a partially-applied transaction in the cache during the brief window
+        // between the first and last `insert`. That's the same relaxed, asynchronous consistency
+        // model the rest of `CachingStore` already offers for a single `set`, not a regression
+        // specific to transactions; true atomic visibility would need its own guard around every
+        // cache read, which would give up the lock-free reads chunk0-3 moved to moka for. A
+        // backing-store rollback also leaves the cache holding values that were rolled back on
+        // the backing store, for the same reason; nothing invalidates those entries, so reads
+        // through this store keep seeing them until they are overwritten, evicted or expire.
+        let mut queue = self.queue.lock().await;
+
+        for op in &transaction.ops {
+            match op {
+                TransactionOp::Set(key, value) => {
+                    self.cache.insert(key.clone(), Some(value.clone())).await;
+                }
+                TransactionOp::Delete(key) => {
+                    self.cache.insert(key.clone(), None).await;
+                }
+            }
+        }
+
+        let inner = self.inner.clone();
+        queue.spawn(async move {
+            let mut applied: Vec<(String, Option<Vec<u8>>)> = Vec::with_capacity(transaction.ops.len());
+
+            for op in transaction.ops {
+                let key = match &op {
+                    TransactionOp::Set(key, _) | TransactionOp::Delete(key) => key.clone(),
+                };
+                let result = match inner.get(&key).await {
+                    Ok(previous) => match &op {
+                        TransactionOp::Set(_, value) => inner.set(&key, value).await,
+                        TransactionOp::Delete(_) => inner.delete(&key).await,
+                    }
+                    .map(|()| previous),
+                    Err(e) => Err(e),
+                };
+
+                match result {
+                    Ok(previous) => applied.push((key, previous)),
+                    Err(e) => {
+                        for (key, previous) in applied.into_iter().rev() {
+                            let rollback = match previous {
+                                Some(value) => inner.set(&key, &value).await,
+                                None => inner.delete(&key).await,
+                            };
+                            if let Err(rollback_err) = rollback {
+                                tracing::warn!(
+                                    "failed to roll back key {key:?} after a failed transaction: {rollback_err:?}"
+                                );
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        if options.durable {
+            // Wait for the task just enqueued (and anything ahead of it) so the guest gets real
+            // feedback on persistence instead of today's fire-and-forget behavior.
+            queue.flush().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`Cas`] that delegates to the backing store's own CAS, bypassing the cache for both the read
+/// and the write, and updating the cache afterwards if the swap succeeds.
+struct CachingCas {
+    inner: Arc<dyn Cas>,
+    cache: Cache<String, Option<Vec<u8>>>,
+    key: String,
+}
+
+#[async_trait]
+impl Cas for CachingCas {
+    async fn current(&self) -> Result<(Option<Vec<u8>>, String), Error> {
+        self.inner.current().await
+    }
+
+    async fn swap(&self, value: Vec<u8>, token: String) -> Result<bool, Error> {
+        let succeeded = self.inner.swap(value.clone(), token).await?;
+
+        if succeeded {
+            self.cache.insert(self.key.clone(), Some(value)).await;
+        }
+
+        Ok(succeeded)
+    }
+}
+
+#[cfg(test)]
+mod commit_tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// A [`Store`] whose `set` takes a moment to land, so tests can observe the difference
+    /// between a durable commit (which waits for it) and a non-durable one (which doesn't).
+    struct DelayedStore {
+        written: AtomicBool,
+    }
+
+    impl DelayedStore {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                written: AtomicBool::new(false),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl Store for DelayedStore {
+        async fn get(&self, _key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(None)
+        }
+
+        async fn set(&self, _key: &str, _value: &[u8]) -> Result<(), Error> {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.written.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn delete(&self, _key: &str) -> Result<(), Error> {
+            unimplemented!()
+        }
+
+        async fn exists(&self, _key: &str) -> Result<bool, Error> {
+            unimplemented!()
+        }
+
+        async fn get_keys(&self) -> Result<Vec<String>, Error> {
+            unimplemented!()
+        }
+
+        async fn new_compare_and_swap(&self, _key: &str) -> Result<Arc<dyn Cas>, Error> {
+            unimplemented!()
+        }
+    }
+
+    fn caching_store(inner: Arc<dyn Store>) -> CachingStore {
+        CachingStore {
+            inner,
+            cache: Cache::builder().max_capacity(1024).build(),
+            queue: Arc::new(AsyncMutex::new(WriteBehindQueue { previous_task: None })),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_durable_commit_waits_for_the_backing_store_write() {
+        let inner = DelayedStore::new();
+        let store = caching_store(inner.clone());
+
+        let mut transaction = store.begin();
+        transaction.set("k", b"v".to_vec());
+        store.commit(transaction, Options { durable: true }).await.unwrap();
+
+        assert!(inner.written.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn a_non_durable_commit_does_not_wait_for_the_backing_store_write() {
+        let inner = DelayedStore::new();
+        let store = caching_store(inner.clone());
+
+        let mut transaction = store.begin();
+        transaction.set("k", b"v".to_vec());
+        store.commit(transaction, Options::default()).await.unwrap();
+
+        assert!(!inner.written.load(Ordering::SeqCst));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(inner.written.load(Ordering::SeqCst));
+    }
+
+    /// A [`Store`] backed by a plain map whose `set` fails for one designated key, just enough to
+    /// exercise `CachingStore::commit`'s rollback-on-failure behavior on the backing store.
+    struct FailingStore {
+        data: std::sync::Mutex<std::collections::HashMap<String, Option<Vec<u8>>>>,
+        fail_on: &'static str,
+    }
+
+    #[async_trait]
+    impl Store for FailingStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.data.lock().unwrap().get(key).cloned().flatten())
+        }
+
+        async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+            if key == self.fail_on {
+                return Err(Error::Other("simulated failure".to_string()));
+            }
+            self.data.lock().unwrap().insert(key.to_string(), Some(value.to_vec()));
+            Ok(())
+        }
+
+        async fn delete(&self, key: &str) -> Result<(), Error> {
+            self.data.lock().unwrap().insert(key.to_string(), None);
+            Ok(())
+        }
+
+        async fn exists(&self, key: &str) -> Result<bool, Error> {
+            Ok(self.get(key).await?.is_some())
+        }
+
+        async fn get_keys(&self) -> Result<Vec<String>, Error> {
+            unimplemented!()
+        }
+
+        async fn new_compare_and_swap(&self, _key: &str) -> Result<Arc<dyn Cas>, Error> {
+            unimplemented!()
+        }
+    }
+
+    #[tokio::test]
+    async fn a_failed_commit_rolls_back_already_applied_ops_on_the_backing_store() {
+        let inner = Arc::new(FailingStore {
+            data: std::sync::Mutex::new(std::collections::HashMap::from([(
+                "a".to_string(),
+                Some(b"orig".to_vec()),
+            )])),
+            fail_on: "b",
+        });
+        let store = caching_store(inner.clone());
+
+        let mut transaction = store.begin();
+        transaction.set("a", b"new".to_vec());
+        transaction.set("b", b"new".to_vec());
+
+        // A durable commit waits for the write-behind task (including its rollback) to finish
+        // before returning, so the backing store is already settled by the time we assert on it.
+        store.commit(transaction, Options { durable: true }).await.unwrap_err();
+
+        assert_eq!(inner.get("a").await.unwrap(), Some(b"orig".to_vec()));
+    }
+}
+
+/// The compression algorithm used to encode a value, as recorded in its header.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompressionAlgorithm {
+    /// Store the value as-is.
+    None,
+    Zstd,
+}
+
+impl CompressionAlgorithm {
+    fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zstd => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::None),
+            1 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// The byte a [`CompressingStoreManager`]-written value starts with, distinguishing it from a
+/// legacy or foreign value that should be passed through undecoded.
+const COMPRESSION_MAGIC: u8 = 0xc5;
+
+/// `magic (1) + algorithm id (1) + uncompressed length (8)`.
+const COMPRESSION_HEADER_LEN: usize = 10;
+
+const DEFAULT_COMPRESSION_THRESHOLD_BYTES: usize = 256;
+const DEFAULT_COMPRESSION_LEVEL: i32 = 0;
+
+#[derive(Clone, Copy)]
+struct Codec {
+    algorithm: CompressionAlgorithm,
+    threshold: usize,
+}
+
+impl Codec {
+    /// Compress `value` if it's at or above the configured threshold, and prepend a header
+    /// recording the algorithm used (or `None` if skipped) and the uncompressed length, so
+    /// `decode` can tell compressed values from legacy, uncompressed ones.
+    fn encode(&self, value: &[u8]) -> Result<Vec<u8>, Error> {
+        let algorithm = if value.len() >= self.threshold {
+            self.algorithm
+        } else {
+            CompressionAlgorithm::None
+        };
+
+        let payload = match algorithm {
+            CompressionAlgorithm::None => value.to_vec(),
+            CompressionAlgorithm::Zstd => zstd::bulk::compress(value, DEFAULT_COMPRESSION_LEVEL)
+                .map_err(|e| Error::Other(format!("compression failed: {e}")))?,
+        };
+
+        let mut encoded = Vec::with_capacity(COMPRESSION_HEADER_LEN + payload.len());
+        encoded.push(COMPRESSION_MAGIC);
+        encoded.push(algorithm.id());
+        encoded.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        encoded.extend_from_slice(&payload);
+        Ok(encoded)
+    }
+
+    /// Reverse [`Codec::encode`].  Values with no recognized header (e.g. written before
+    /// compression was enabled) are returned unchanged.
+    fn decode(&self, value: Vec<u8>) -> Result<Vec<u8>, Error> {
+        if value.first() != Some(&COMPRESSION_MAGIC) || value.len() < COMPRESSION_HEADER_LEN {
+            return Ok(value);
+        }
+
+        let algorithm = CompressionAlgorithm::from_id(value[1])
+            .ok_or_else(|| Error::Other("value has an unrecognized compression algorithm".to_string()))?;
+        let uncompressed_len = u64::from_le_bytes(value[2..10].try_into().unwrap()) as usize;
+        let payload = &value[COMPRESSION_HEADER_LEN..];
+
+        match algorithm {
+            CompressionAlgorithm::None => Ok(payload.to_vec()),
+            CompressionAlgorithm::Zstd => zstd::bulk::decompress(payload, uncompressed_len)
+                .map_err(|e| Error::Other(format!("decompression failed: {e}"))),
+        }
+    }
+}
+
+/// Wrap each `Store` produced by the inner `StoreManager` so values are transparently compressed
+/// on write and decompressed on read, as Garage's block manager does with zstd.
+///
+/// Compression is threshold-gated: values smaller than `threshold` are stored as-is, since
+/// compressing small values tends to lose more to header and framing overhead than it saves.
+/// Each stored value carries a small self-describing header (magic byte, algorithm id,
+/// uncompressed length) so a read can detect a value written before compression was enabled (or
+/// by something else entirely) and pass it through unchanged, and so the algorithm can evolve
+/// without a migration.
+pub struct CompressingStoreManager<T> {
+    inner: T,
+    codec: Codec,
+}
+
+impl<T> CompressingStoreManager<T> {
+    pub fn new(inner: T) -> Self {
+        Self::new_with_options(
+            CompressionAlgorithm::Zstd,
+            DEFAULT_COMPRESSION_THRESHOLD_BYTES,
+            inner,
+        )
+    }
+
+    pub fn new_with_options(algorithm: CompressionAlgorithm, threshold: usize, inner: T) -> Self {
+        Self {
+            inner,
+            codec: Codec { algorithm, threshold },
+        }
+    }
+}
+
+#[async_trait]
+impl<T: StoreManager> StoreManager for CompressingStoreManager<T> {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        Ok(Arc::new(CompressingStore {
+            inner: self.inner.get(name).await?,
+            codec: self.codec,
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.inner.is_defined(store_name)
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.inner.summary(store_name)
+    }
+}
+
+struct CompressingStore {
+    inner: Arc<dyn Store>,
+    codec: Codec,
+}
+
+#[async_trait]
+impl Store for CompressingStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.inner
+            .get(key)
+            .await?
+            .map(|value| self.codec.decode(value))
+            .transpose()
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.inner.set(key, &self.codec.encode(value)?).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        self.inner.exists(key).await
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        self.inner.get_keys().await
     }
 
-    async fn new_compare_and_swap(&self, key: &str) -> anyhow::Result<Arc<dyn Cas>, Error> {
-        todo!()
+    async fn get_many(&self, keys: Vec<String>) -> Result<Vec<Option<(String, Vec<u8>)>>, Error> {
+        self.inner
+            .get_many(keys)
+            .await?
+            .into_iter()
+            .map(|entry| {
+                entry
+                    .map(|(key, value)| Ok((key, self.codec.decode(value)?)))
+                    .transpose()
+            })
+            .collect()
+    }
+
+    async fn set_many(&self, key_values: Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+        let key_values = key_values
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.codec.encode(&value)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.inner.set_many(key_values).await
+    }
+
+    async fn delete_many(&self, keys: Vec<String>) -> Result<(), Error> {
+        self.inner.delete_many(keys).await
+    }
+
+    async fn increment(&self, key: String, delta: i64) -> Result<i64, Error> {
+        // Go through this store's own `get`/`set` rather than `inner.increment`, since the inner
+        // store holds the encoded (header-prefixed) bytes, which it cannot parse as an integer.
+        let new_value = parse_current(self.get(&key).await?)? + delta;
+        self.set(&key, new_value.to_string().as_bytes()).await?;
+        Ok(new_value)
+    }
+
+    async fn new_compare_and_swap(&self, key: &str) -> Result<Arc<dyn Cas>, Error> {
+        Ok(Arc::new(CompressingCas {
+            inner: self.inner.new_compare_and_swap(key).await?,
+            codec: self.codec,
+        }))
+    }
+
+    async fn list_keys(
+        &self,
+        cursor: Option<String>,
+        limit: Option<u64>,
+    ) -> Result<(Vec<String>, Option<String>), Error> {
+        self.inner.list_keys(cursor, limit).await
+    }
+}
+
+/// A [`Cas`] that compresses/decompresses the value passing through it the same way
+/// [`CompressingStore`] does, delegating the actual compare-and-swap to the backing store.
+struct CompressingCas {
+    inner: Arc<dyn Cas>,
+    codec: Codec,
+}
+
+#[async_trait]
+impl Cas for CompressingCas {
+    async fn current(&self) -> Result<(Option<Vec<u8>>, String), Error> {
+        let (value, token) = self.inner.current().await?;
+        Ok((value.map(|value| self.codec.decode(value)).transpose()?, token))
+    }
+
+    async fn swap(&self, value: Vec<u8>, token: String) -> Result<bool, Error> {
+        self.inner.swap(self.codec.encode(&value)?, token).await
+    }
+}
+
+#[cfg(test)]
+mod codec_tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_values_above_the_threshold() {
+        let codec = Codec {
+            algorithm: CompressionAlgorithm::Zstd,
+            threshold: 16,
+        };
+
+        let value = vec![b'x'; 1024];
+        let encoded = codec.encode(&value).unwrap();
+        assert_eq!(codec.decode(encoded).unwrap(), value);
+    }
+
+    #[test]
+    fn stores_values_below_the_threshold_uncompressed_but_still_round_trips() {
+        let codec = Codec {
+            algorithm: CompressionAlgorithm::Zstd,
+            threshold: 1024,
+        };
+
+        let value = b"short".to_vec();
+        let encoded = codec.encode(&value).unwrap();
+        assert_eq!(codec.decode(encoded).unwrap(), value);
+    }
+
+    #[test]
+    fn passes_through_legacy_values_with_no_recognized_header() {
+        let codec = Codec {
+            algorithm: CompressionAlgorithm::Zstd,
+            threshold: 0,
+        };
+
+        let legacy = b"written before compression was enabled".to_vec();
+        assert_eq!(codec.decode(legacy.clone()).unwrap(), legacy);
+    }
+}
+
+/// A [`StoreManager`] that replicates a single logical store label across `N` delegate
+/// `StoreManager`s and serves it via quorum reads/writes, the same resync/repair pattern
+/// Garage's block manager uses to keep replicas converged.
+///
+/// `write_quorum` replicas must acknowledge a `set`/`delete` before it returns (the rest are
+/// written in the background, best-effort), and `read_quorum` replicas are consulted for a
+/// `get`, which returns the value most of them agree on and kicks off a background read-repair
+/// write to any replica found to be stale. Choosing `read_quorum + write_quorum > N` guarantees
+/// every read overlaps with every prior write's quorum.
+pub struct ReplicatingStoreManager {
+    delegates: Vec<Arc<dyn StoreManager>>,
+    read_quorum: usize,
+    write_quorum: usize,
+}
+
+impl ReplicatingStoreManager {
+    /// # Panics
+    ///
+    /// Panics if `delegates` is empty or if `read_quorum`/`write_quorum` is zero or exceeds the
+    /// number of delegates.
+    pub fn new(delegates: Vec<Arc<dyn StoreManager>>, read_quorum: usize, write_quorum: usize) -> Self {
+        let replica_count = delegates.len();
+        assert!(replica_count > 0, "ReplicatingStoreManager needs at least one delegate");
+        assert!(
+            (1..=replica_count).contains(&read_quorum),
+            "read_quorum must be between 1 and the delegate count"
+        );
+        assert!(
+            (1..=replica_count).contains(&write_quorum),
+            "write_quorum must be between 1 and the delegate count"
+        );
+
+        if read_quorum + write_quorum <= replica_count {
+            tracing::warn!(
+                "ReplicatingStoreManager configured with read_quorum + write_quorum <= delegate \
+                 count ({replica_count}); reads are not guaranteed to see the most recent write"
+            );
+        }
+
+        Self {
+            delegates,
+            read_quorum,
+            write_quorum,
+        }
+    }
+}
+
+#[async_trait]
+impl StoreManager for ReplicatingStoreManager {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let mut replicas = Vec::with_capacity(self.delegates.len());
+        for delegate in &self.delegates {
+            replicas.push(delegate.get(name).await?);
+        }
+
+        Ok(Arc::new(ReplicatingStore {
+            replicas,
+            read_quorum: self.read_quorum,
+            write_quorum: self.write_quorum,
+        }))
+    }
+
+    fn is_defined(&self, store_name: &str) -> bool {
+        self.delegates.iter().all(|delegate| delegate.is_defined(store_name))
+    }
+
+    fn summary(&self, store_name: &str) -> Option<String> {
+        self.delegates.first().and_then(|delegate| delegate.summary(store_name))
+    }
+}
+
+/// Of the replicas that answered, pick the value most of them agree on, breaking ties by
+/// whichever value was observed first.
+///
+/// This deliberately does *not* pick a "newest" value by comparing [`Cas`] tokens: a token is
+/// only guaranteed comparable for *equality* by the `Cas` contract (host.rs), not for ordering --
+/// a backend whose token is, say, a plain incrementing counter rendered as a decimal string would
+/// have `"9"` sort after `"10"`, so picking the lexicographically greatest token would silently
+/// read-repair the correct value away. Majority vote needs no such assumption, at the cost of not
+/// resolving a genuine tie (e.g. exactly two replicas each disagreeing) any better than
+/// arbitrarily; callers relying on a true last-writer-wins order need tokens with a well-defined
+/// ordering and should compare those directly instead.
+fn majority_value(reads: &[Option<(Option<Vec<u8>>, String)>]) -> Option<Option<Vec<u8>>> {
+    let mut counts: Vec<(Option<Vec<u8>>, usize)> = Vec::new();
+
+    for value in reads.iter().flatten().map(|(value, _)| value.clone()) {
+        match counts.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+
+    // `Iterator::max_by_key` returns the *last* maximal element on a tie, which would break ties
+    // by whichever value was observed last -- the opposite of this function's contract. Fold
+    // manually instead, only replacing the current winner on a strictly greater count, so the
+    // first-seen value wins ties.
+    let mut winner: Option<(Option<Vec<u8>>, usize)> = None;
+    for (value, count) in counts {
+        let replace = match &winner {
+            Some((_, best_count)) => count > *best_count,
+            None => true,
+        };
+        if replace {
+            winner = Some((value, count));
+        }
+    }
+
+    winner.map(|(value, _)| value)
+}
+
+/// Read `key` from the first `read_quorum` replicas (skipping ones that error), and return the
+/// majority value along with each responding replica's index and own [`Cas`] token (so a
+/// [`ReplicatingCas::swap`] can later compare-and-swap each replica against its own token) and the
+/// indices of replicas that disagreed with the majority value.
+async fn quorum_read_cas(
+    replicas: &[Arc<dyn Store>],
+    read_quorum: usize,
+    key: &str,
+) -> Result<(Option<Vec<u8>>, Vec<(usize, String)>, Vec<usize>), Error> {
+    let mut reads = Vec::with_capacity(read_quorum);
+
+    for replica in replicas.iter().take(read_quorum) {
+        let read = match replica.new_compare_and_swap(key).await {
+            Ok(cas) => cas.current().await,
+            Err(e) => Err(e),
+        };
+        match read {
+            Ok(read) => reads.push(Some(read)),
+            Err(e) => {
+                tracing::warn!("replica read failed during quorum read of {key:?}: {e:?}");
+                reads.push(None);
+            }
+        }
+    }
+
+    let winner = majority_value(&reads)
+        .ok_or_else(|| Error::Other(format!("no replica responded for key {key:?}")))?;
+
+    let stale = reads
+        .iter()
+        .enumerate()
+        .filter(|(_, read)| !matches!(read, Some((value, _)) if *value == winner))
+        .map(|(index, _)| index)
+        .collect();
+
+    let tokens = reads
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, read)| read.map(|(_, token)| (index, token)))
+        .collect();
+
+    Ok((winner, tokens, stale))
+}
+
+/// Read `key` from the first `read_quorum` replicas and return the majority value along with the
+/// indices of replicas that disagreed with it. A thin wrapper over [`quorum_read_cas`] for
+/// callers (like a plain `get`) that don't need each replica's own token back.
+async fn quorum_read(
+    replicas: &[Arc<dyn Store>],
+    read_quorum: usize,
+    key: &str,
+) -> Result<(Option<Vec<u8>>, Vec<usize>), Error> {
+    let (value, _tokens, stale) = quorum_read_cas(replicas, read_quorum, key).await?;
+    Ok((value, stale))
+}
+
+/// Write `value` (or delete, if `None`) to every replica, returning once `write_quorum` of them
+/// have acknowledged. The remaining replicas are left to finish in the background so they
+/// eventually converge even though the caller doesn't wait for them.
+async fn quorum_write(
+    replicas: &[Arc<dyn Store>],
+    write_quorum: usize,
+    key: &str,
+    value: Option<Vec<u8>>,
+) -> Result<(), Error> {
+    // Each replica's write is sent back over `tx` as soon as it lands, rather than awaited in
+    // spawn order, so a slow or stuck replica can't hold up quorum once enough others have acked.
+    // The tasks are left detached (not collected into `JoinHandle`s) so that returning early,
+    // once `write_quorum` is reached, doesn't need to cancel or wait on the stragglers -- they
+    // keep running in the background and either land or get dropped by `tx.send` failing, which
+    // we ignore since nothing is listening anymore at that point.
+    let (tx, mut rx) = mpsc::channel(replicas.len().max(1));
+
+    for replica in replicas.iter().cloned() {
+        let key = key.to_owned();
+        let value = value.clone();
+        let tx = tx.clone();
+        task::spawn(
+            async move {
+                let result = match value {
+                    Some(value) => replica.set(&key, &value).await,
+                    None => replica.delete(&key).await,
+                };
+                let _ = tx.send(result).await;
+            }
+            .in_current_span(),
+        );
+    }
+    drop(tx);
+
+    let mut acked = 0;
+    let mut last_err = None;
+
+    while let Some(result) = rx.recv().await {
+        match result {
+            Ok(()) => {
+                acked += 1;
+                if acked >= write_quorum {
+                    return Ok(());
+                }
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        Error::Other(format!(
+            "only {acked} of {} replicas acknowledged the write to {key:?}",
+            replicas.len()
+        ))
+    }))
+}
+
+/// Write `value` back to each replica named in `stale` in the background, without making the
+/// caller wait for it.
+fn spawn_read_repair(replicas: &[Arc<dyn Store>], stale: Vec<usize>, key: String, value: Option<Vec<u8>>) {
+    for index in stale {
+        let replica = replicas[index].clone();
+        let key = key.clone();
+        let value = value.clone();
+        task::spawn(
+            async move {
+                let result = match value {
+                    Some(value) => replica.set(&key, &value).await,
+                    None => replica.delete(&key).await,
+                };
+                if let Err(e) = result {
+                    tracing::warn!("read-repair write failed for key {key:?}: {e:?}");
+                }
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// Encode each replica's own [`Cas`] token as a single opaque string for [`ReplicatingCas`] to
+/// hand back to its caller, who must treat it as opaque and pass it back unmodified.
+///
+/// Each token is stored length-prefixed (`index:length:token`, comma-separated) rather than with
+/// a reserved delimiter, since a replica's token is itself opaque and may contain any character.
+fn encode_replica_tokens(tokens: &[(usize, String)]) -> String {
+    tokens
+        .iter()
+        .map(|(index, token)| format!("{index}:{}:{token}", token.len()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Reverse [`encode_replica_tokens`].
+fn decode_replica_tokens(token: &str) -> Result<Vec<(usize, String)>, Error> {
+    let malformed = || Error::Other("malformed replica token".to_string());
+
+    let mut result = Vec::new();
+    let mut rest = token;
+
+    while !rest.is_empty() {
+        let (index, after_index) = rest.split_once(':').ok_or_else(malformed)?;
+        let (len, after_len) = after_index.split_once(':').ok_or_else(malformed)?;
+        let index: usize = index.parse().map_err(|_| malformed())?;
+        let len: usize = len.parse().map_err(|_| malformed())?;
+
+        if after_len.len() < len {
+            return Err(malformed());
+        }
+        let (replica_token, remainder) = after_len.split_at(len);
+
+        result.push((index, replica_token.to_string()));
+        rest = remainder.strip_prefix(',').unwrap_or(remainder);
+    }
+
+    Ok(result)
+}
+
+struct ReplicatingStore {
+    replicas: Vec<Arc<dyn Store>>,
+    read_quorum: usize,
+    write_quorum: usize,
+}
+
+#[async_trait]
+impl Store for ReplicatingStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let (value, stale) = quorum_read(&self.replicas, self.read_quorum, key).await?;
+
+        if !stale.is_empty() {
+            spawn_read_repair(&self.replicas, stale, key.to_owned(), value.clone());
+        }
+
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        quorum_write(&self.replicas, self.write_quorum, key, Some(value.to_owned())).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        quorum_write(&self.replicas, self.write_quorum, key, None).await
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.get(key).await?.is_some())
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        // Union the keys every replica knows about rather than trusting a single one, since a
+        // lagging replica may be missing keys a quorum read would otherwise repair on demand.
+        let mut keys = HashSet::new();
+        for replica in &self.replicas {
+            keys.extend(replica.get_keys().await?);
+        }
+        Ok(keys.into_iter().collect())
+    }
+
+    async fn new_compare_and_swap(&self, key: &str) -> Result<Arc<dyn Cas>, Error> {
+        Ok(Arc::new(ReplicatingCas {
+            replicas: self.replicas.clone(),
+            read_quorum: self.read_quorum,
+            write_quorum: self.write_quorum,
+            key: key.to_owned(),
+        }))
+    }
+}
+
+/// A [`Cas`] over a [`ReplicatingStore`]'s quorum: `current` does a quorum read and encodes each
+/// responding replica's own token, and `swap` compare-and-swaps each of those replicas through
+/// its *own* [`Cas`], so a replica whose value has changed since `current` is correctly rejected
+/// rather than blindly overwritten.
+struct ReplicatingCas {
+    replicas: Vec<Arc<dyn Store>>,
+    read_quorum: usize,
+    write_quorum: usize,
+    key: String,
+}
+
+#[async_trait]
+impl Cas for ReplicatingCas {
+    async fn current(&self) -> Result<(Option<Vec<u8>>, String), Error> {
+        // `swap` needs a per-replica token for every replica it might have to CAS, so read at
+        // least `write_quorum` of them even when `read_quorum` is smaller -- otherwise, with
+        // write_quorum > read_quorum, `swap` would never see enough tokens to reach its own
+        // quorum.
+        let quorum = self.read_quorum.max(self.write_quorum);
+        let (value, tokens, stale) = quorum_read_cas(&self.replicas, quorum, &self.key).await?;
+
+        if !stale.is_empty() {
+            spawn_read_repair(&self.replicas, stale, self.key.clone(), value.clone());
+        }
+
+        Ok((value, encode_replica_tokens(&tokens)))
+    }
+
+    async fn swap(&self, value: Vec<u8>, token: String) -> Result<bool, Error> {
+        let expected = decode_replica_tokens(&token)?;
+
+        let mut acked = 0;
+
+        for (index, expected_token) in expected {
+            let Some(replica) = self.replicas.get(index) else {
+                continue;
+            };
+
+            let cas = match replica.new_compare_and_swap(&self.key).await {
+                Ok(cas) => cas,
+                Err(e) => {
+                    tracing::warn!("replica CAS setup failed for key {:?}: {e:?}", self.key);
+                    continue;
+                }
+            };
+
+            match cas.swap(value.clone(), expected_token).await {
+                Ok(true) => {
+                    acked += 1;
+                    if acked >= self.write_quorum {
+                        return Ok(true);
+                    }
+                }
+                Ok(false) => {
+                    // This replica's value has moved on since `current` observed it; it does not
+                    // count toward the write quorum, and we don't overwrite it blindly.
+                }
+                Err(e) => tracing::warn!("replica CAS swap failed for key {:?}: {e:?}", self.key),
+            }
+        }
+
+        Ok(acked >= self.write_quorum)
+    }
+}
+
+#[cfg(test)]
+mod replicating_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A minimal in-memory [`Store`] with real CAS semantics (a monotonically increasing
+    /// per-key version as the causality token) -- just enough to exercise
+    /// [`ReplicatingStore`]/[`ReplicatingCas`] without a real backend.
+    struct InMemoryStore {
+        data: Arc<Mutex<HashMap<String, (Option<Vec<u8>>, u64)>>>,
+    }
+
+    impl InMemoryStore {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                data: Arc::new(Mutex::new(HashMap::new())),
+            })
+        }
+
+        fn seed(&self, key: &str, value: &[u8]) {
+            self.data.lock().unwrap().insert(key.to_string(), (Some(value.to_vec()), 1));
+        }
+    }
+
+    struct InMemoryCas {
+        data: Arc<Mutex<HashMap<String, (Option<Vec<u8>>, u64)>>>,
+        key: String,
+    }
+
+    #[async_trait]
+    impl Cas for InMemoryCas {
+        async fn current(&self) -> Result<(Option<Vec<u8>>, String), Error> {
+            let data = self.data.lock().unwrap();
+            match data.get(&self.key) {
+                Some((value, version)) => Ok((value.clone(), version.to_string())),
+                None => Ok((None, "0".to_string())),
+            }
+        }
+
+        async fn swap(&self, value: Vec<u8>, token: String) -> Result<bool, Error> {
+            let mut data = self.data.lock().unwrap();
+            let current_version = data.get(&self.key).map(|(_, version)| *version).unwrap_or(0);
+            if current_version.to_string() != token {
+                return Ok(false);
+            }
+            data.insert(self.key.clone(), (Some(value), current_version + 1));
+            Ok(true)
+        }
+    }
+
+    #[async_trait]
+    impl Store for InMemoryStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.data.lock().unwrap().get(key).and_then(|(value, _)| value.clone()))
+        }
+
+        async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+            let mut data = self.data.lock().unwrap();
+            let version = data.get(key).map(|(_, version)| version + 1).unwrap_or(1);
+            data.insert(key.to_string(), (Some(value.to_vec()), version));
+            Ok(())
+        }
+
+        async fn delete(&self, key: &str) -> Result<(), Error> {
+            let mut data = self.data.lock().unwrap();
+            let version = data.get(key).map(|(_, version)| version + 1).unwrap_or(1);
+            data.insert(key.to_string(), (None, version));
+            Ok(())
+        }
+
+        async fn exists(&self, key: &str) -> Result<bool, Error> {
+            Ok(self.get(key).await?.is_some())
+        }
+
+        async fn get_keys(&self) -> Result<Vec<String>, Error> {
+            Ok(self.data.lock().unwrap().keys().cloned().collect())
+        }
+
+        async fn new_compare_and_swap(&self, key: &str) -> Result<Arc<dyn Cas>, Error> {
+            Ok(Arc::new(InMemoryCas {
+                data: self.data.clone(),
+                key: key.to_string(),
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn get_repairs_a_stale_replica_in_the_background() {
+        let fresh = InMemoryStore::new();
+        let stale = InMemoryStore::new();
+        fresh.seed("k", b"new");
+        stale.seed("k", b"old");
+
+        let store = ReplicatingStore {
+            replicas: vec![fresh.clone(), stale.clone()],
+            read_quorum: 2,
+            write_quorum: 2,
+        };
+
+        let value = store.get("k").await.unwrap();
+        assert!(value == Some(b"new".to_vec()) || value == Some(b"old".to_vec()));
+
+        // Give the background read-repair task a chance to run.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(stale.get("k").await.unwrap(), fresh.get("k").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn swap_detects_a_conflicting_concurrent_write_instead_of_clobbering_it() {
+        let a = InMemoryStore::new();
+        let b = InMemoryStore::new();
+        a.seed("k", b"v1");
+        b.seed("k", b"v1");
+
+        let store = ReplicatingStore {
+            replicas: vec![a.clone(), b.clone()],
+            read_quorum: 2,
+            write_quorum: 2,
+        };
+
+        let cas = store.new_compare_and_swap("k").await.unwrap();
+        let (_, token) = cas.current().await.unwrap();
+
+        // A concurrent writer updates both replicas directly, invalidating the token `cas` holds.
+        a.set("k", b"v2").await.unwrap();
+        b.set("k", b"v2").await.unwrap();
+
+        assert!(!cas.swap(b"v3".to_vec(), token).await.unwrap());
+        assert_eq!(a.get("k").await.unwrap(), Some(b"v2".to_vec()));
+        assert_eq!(b.get("k").await.unwrap(), Some(b"v2".to_vec()));
     }
 }